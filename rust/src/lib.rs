@@ -1,9 +1,9 @@
 use std::ffi::{CStr, CString};
-use std::os::raw::{c_char, c_int};
+use std::os::raw::{c_char, c_int, c_void};
 use std::ptr;
 use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
 use serde_json;
-use anyhow::Result;
 use matchit::Router as MatchitRouter;
 use ahash::AHashMap;
 
@@ -12,6 +12,7 @@ pub struct RouterResult {
     pub data: *mut c_char,
     pub len: usize,
     pub status: c_int,
+    pub handle: *mut c_void,
 }
 
 impl RouterResult {
@@ -22,6 +23,17 @@ impl RouterResult {
             data: c_string.into_raw(),
             len,
             status: 0,
+            handle: ptr::null_mut(),
+        })
+    }
+
+    // Used by `router_create` to hand back the opaque router handle.
+    fn with_handle(handle: *mut c_void) -> Box<RouterResult> {
+        Box::new(RouterResult {
+            data: ptr::null_mut(),
+            len: 0,
+            status: 0,
+            handle,
         })
     }
 
@@ -30,66 +42,225 @@ impl RouterResult {
             data: ptr::null_mut(),
             len: 0,
             status,
+            handle: ptr::null_mut(),
+        })
+    }
+
+    // Like `error`, but carries a JSON payload (e.g. the conflicting route)
+    // alongside the negative status code.
+    fn error_with_data(status: c_int, data: String) -> Box<RouterResult> {
+        let c_string = CString::new(data).unwrap_or_else(|_| CString::new("").unwrap());
+        let len = c_string.as_bytes().len();
+        Box::new(RouterResult {
+            data: c_string.into_raw(),
+            len,
+            status,
+            handle: ptr::null_mut(),
         })
     }
 }
 
 pub struct Router {
-    routers: AHashMap<String, MatchitRouter<String>>,
-    route_cache: AHashMap<String, String>,
-    max_cache_size: usize,
+    routers: AHashMap<String, MatchitRouter<RouteMeta>>,
+    route_cache: Mutex<RouteCache>,
+    fallback: Option<RouteMeta>,
+}
+
+// Method bucket used for routes registered with "*" or "ANY", checked after
+// the exact-method router and before the fallback handler.
+const ANY_METHOD: &str = "ANY";
+
+// Everything a matched route can carry besides the bare handler id, so a
+// framework can resolve named routes and assemble a middleware pipeline from
+// a single `router_match` lookup instead of a second registry.
+#[derive(Clone, Default, serde::Serialize)]
+struct RouteMeta {
+    handler: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    middleware: Vec<String>,
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    constraints: HashMap<String, String>,
+}
+
+impl RouteMeta {
+    fn new(handler: &str) -> Self {
+        Self {
+            handler: handler.to_string(),
+            ..Default::default()
+        }
+    }
+}
+
+// A matched route, cheaply clonable out of the cache without re-parsing JSON.
+type CachedMatch = Arc<(RouteMeta, Vec<(String, String)>)>;
+
+// One slot of the LRU's intrusive doubly linked list, stored in a slab
+// (`RouteCache::nodes`) so eviction can reuse an index instead of
+// reallocating.
+struct CacheNode {
+    key: (String, String),
+    value: CachedMatch,
+    prev: Option<usize>,
+    next: Option<usize>,
+}
+
+// A real LRU keyed by `(method, path)`: `map` gives O(1) lookup of a node's
+// slab index, and the intrusive list (`head` = most recently used, `tail` =
+// least) gives O(1) promotion and eviction with no JSON round-trip. Lives
+// behind its own `Mutex` so matching can stay a read-locked operation on
+// `Router` while still bumping the LRU order and hit/miss counters.
+struct RouteCache {
+    map: AHashMap<(String, String), usize>,
+    nodes: Vec<CacheNode>,
+    head: Option<usize>,
+    tail: Option<usize>,
+    capacity: usize,
+    hits: u64,
+    misses: u64,
+}
+
+impl RouteCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            map: AHashMap::with_capacity(capacity),
+            nodes: Vec::with_capacity(capacity),
+            head: None,
+            tail: None,
+            capacity,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    fn detach(&mut self, idx: usize) {
+        let (prev, next) = (self.nodes[idx].prev, self.nodes[idx].next);
+        match prev {
+            Some(p) => self.nodes[p].next = next,
+            None => self.head = next,
+        }
+        match next {
+            Some(n) => self.nodes[n].prev = prev,
+            None => self.tail = prev,
+        }
+    }
+
+    fn attach_front(&mut self, idx: usize) {
+        self.nodes[idx].prev = None;
+        self.nodes[idx].next = self.head;
+        if let Some(h) = self.head {
+            self.nodes[h].prev = Some(idx);
+        }
+        self.head = Some(idx);
+        if self.tail.is_none() {
+            self.tail = Some(idx);
+        }
+    }
+
+    fn get(&mut self, key: &(String, String)) -> Option<CachedMatch> {
+        match self.map.get(key).copied() {
+            Some(idx) => {
+                self.hits += 1;
+                self.detach(idx);
+                self.attach_front(idx);
+                Some(self.nodes[idx].value.clone())
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    fn insert(&mut self, key: (String, String), value: CachedMatch) {
+        if let Some(&idx) = self.map.get(&key) {
+            self.nodes[idx].value = value;
+            self.detach(idx);
+            self.attach_front(idx);
+            return;
+        }
+
+        if self.capacity == 0 {
+            return;
+        }
+
+        if self.nodes.len() < self.capacity {
+            let idx = self.nodes.len();
+            self.nodes.push(CacheNode { key: key.clone(), value, prev: None, next: None });
+            self.map.insert(key, idx);
+            self.attach_front(idx);
+        } else if let Some(tail_idx) = self.tail {
+            // Evict the least-recently-used entry and reuse its slot.
+            self.detach(tail_idx);
+            self.map.remove(&self.nodes[tail_idx].key);
+            self.nodes[tail_idx].key = key.clone();
+            self.nodes[tail_idx].value = value;
+            self.map.insert(key, tail_idx);
+            self.attach_front(tail_idx);
+        }
+    }
+
+    fn clear(&mut self) {
+        self.map.clear();
+        self.nodes.clear();
+        self.head = None;
+        self.tail = None;
+    }
+
+    fn len(&self) -> usize {
+        self.nodes.len()
+    }
 }
 
 impl Router {
     fn new() -> Self {
         Self {
             routers: AHashMap::new(),
-            route_cache: AHashMap::with_capacity(1000),
-            max_cache_size: 1000,
+            route_cache: Mutex::new(RouteCache::new(1000)),
+            fallback: None,
         }
     }
 
-    fn add_route(&mut self, method: &str, path: &str, handler_id: &str) -> Result<()> {
+    fn add_route(&mut self, method: &str, path: &str, meta: RouteMeta) -> Result<(), matchit::InsertError> {
+        let method = if method == "*" { ANY_METHOD } else { method };
         let router = self.routers.entry(method.to_string()).or_insert_with(MatchitRouter::new);
-        router.insert(path, handler_id.to_string())?;
-        Ok(())
+        router.insert(path, meta)
+    }
+
+    fn set_fallback(&mut self, handler_id: &str) {
+        self.fallback = Some(RouteMeta::new(handler_id));
     }
 
-    fn match_route(&mut self, method: &str, path: &str) -> Option<(String, Vec<(String, String)>)> {
+    fn match_route(&self, method: &str, path: &str) -> Option<(RouteMeta, Vec<(String, String)>)> {
         // Check cache first
-        let cache_key = format!("{}:{}", method, path);
-        if let Some(cached) = self.route_cache.get(&cache_key) {
-            if let Ok(result) = serde_json::from_str::<(String, Vec<(String, String)>)>(cached) {
-                return Some(result);
-            }
+        let cache_key = (method.to_string(), path.to_string());
+        if let Some(cached) = self.route_cache.lock().unwrap().get(&cache_key) {
+            return Some((*cached).clone());
         }
 
-        // Perform actual matching
-        if let Some(router) = self.routers.get(method) {
-            if let Ok(matched) = router.at(path) {
-                let handler_id = matched.value.clone();
-                let params: Vec<(String, String)> = matched.params.iter()
-                    .map(|(k, v)| (k.to_string(), v.to_string()))
-                    .collect();
-
-                let result = (handler_id, params);
-
-                // Cache the result
-                if self.route_cache.len() < self.max_cache_size {
-                    if let Ok(serialized) = serde_json::to_string(&result) {
-                        self.route_cache.insert(cache_key, serialized);
-                    }
-                }
-
-                return Some(result);
-            }
+        // Perform actual matching: exact method, then the ANY bucket, then
+        // the global fallback handler (with no params) if one is set.
+        let matched = self.routers.get(method)
+            .and_then(|router| router.at(path).ok())
+            .or_else(|| self.routers.get(ANY_METHOD).and_then(|router| router.at(path).ok()));
+
+        if let Some(matched) = matched {
+            let meta = matched.value.clone();
+            let params: Vec<(String, String)> = matched.params.iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect();
+
+            let result = Arc::new((meta, params));
+            self.route_cache.lock().unwrap().insert(cache_key, result.clone());
+            return Some((*result).clone());
         }
 
-        None
+        self.fallback.clone().map(|meta| (meta, Vec::new()))
     }
 
     fn clear_cache(&mut self) {
-        self.route_cache.clear();
+        self.route_cache.lock().unwrap().clear();
     }
 
     fn get_stats(&self) -> RouterStats {
@@ -100,11 +271,14 @@ impl Router {
             route_count += 1; // We'll count routers instead
         }
 
+        let cache = self.route_cache.lock().unwrap();
         RouterStats {
             method_count: self.routers.len(),
             route_count,
-            cache_size: self.route_cache.len(),
-            cache_capacity: self.max_cache_size,
+            cache_size: cache.len(),
+            cache_capacity: cache.capacity,
+            cache_hits: cache.hits,
+            cache_misses: cache.misses,
         }
     }
 }
@@ -115,25 +289,37 @@ struct RouterStats {
     route_count: usize,
     cache_size: usize,
     cache_capacity: usize,
+    cache_hits: u64,
+    cache_misses: u64,
 }
 
-static mut ROUTER: Option<Router> = None;
-static mut ROUTER_INIT: std::sync::Once = std::sync::Once::new();
+// Opaque handle type returned by `router_create`. Each handle owns an
+// independent routing table, so multiple instances (e.g. per-vhost) can
+// live in the same process. `match_route` only needs a read lock, so
+// concurrent matches share the router (they still serialize briefly on
+// the route cache's `Mutex`); mutating calls take the write lock.
+type RouterHandle = RwLock<Router>;
 
-fn get_router() -> &'static mut Router {
-    unsafe {
-        ROUTER_INIT.call_once(|| {
-            ROUTER = Some(Router::new());
-        });
-        ROUTER.as_mut().unwrap()
-    }
+unsafe fn handle_ref<'a>(handle: *mut c_void) -> Option<&'a RouterHandle> {
+    (handle as *const RouterHandle).as_ref()
 }
 
-/// Create a new router instance
+/// Create a new, independent router instance and return its opaque handle.
 #[no_mangle]
 pub extern "C" fn router_create() -> *mut RouterResult {
+    let handle = Box::into_raw(Box::new(RwLock::new(Router::new()))) as *mut c_void;
+    Box::into_raw(RouterResult::with_handle(handle))
+}
+
+/// Destroy a router instance created by `router_create`.
+#[no_mangle]
+pub extern "C" fn router_destroy(handle: *mut c_void) -> *mut RouterResult {
+    if handle.is_null() {
+        return Box::into_raw(RouterResult::error(-1));
+    }
+
     unsafe {
-        ROUTER = Some(Router::new());
+        drop(Box::from_raw(handle as *mut RouterHandle));
     }
     Box::into_raw(RouterResult::success("OK".to_string()))
 }
@@ -141,6 +327,7 @@ pub extern "C" fn router_create() -> *mut RouterResult {
 /// Add a route to the router
 #[no_mangle]
 pub extern "C" fn router_add_route(
+    handle: *mut c_void,
     method: *const c_char,
     path: *const c_char,
     handler_id: *const c_char,
@@ -149,6 +336,11 @@ pub extern "C" fn router_add_route(
         return Box::into_raw(RouterResult::error(-1));
     }
 
+    let router = match unsafe { handle_ref(handle) } {
+        Some(router) => router,
+        None => return Box::into_raw(RouterResult::error(-1)),
+    };
+
     let method_str = match unsafe { CStr::from_ptr(method) }.to_str() {
         Ok(s) => s,
         Err(_) => return Box::into_raw(RouterResult::error(-2)),
@@ -164,16 +356,158 @@ pub extern "C" fn router_add_route(
         Err(_) => return Box::into_raw(RouterResult::error(-4)),
     };
 
-    let router = get_router();
-    match router.add_route(method_str, path_str, handler_str) {
+    let mut router = router.write().unwrap();
+    match router.add_route(method_str, path_str, RouteMeta::new(handler_str)) {
         Ok(_) => Box::into_raw(RouterResult::success("OK".to_string())),
+        // A catch-all segment (`{*name}`) followed by more path segments is
+        // ambiguous, so it gets its own error code instead of the generic -5.
+        Err(matchit::InsertError::InvalidCatchAll) => Box::into_raw(RouterResult::error(-6)),
+        // Overlapping routes (e.g. `/users/{id}` vs `/users/{name}`) get a
+        // dedicated code and carry the conflicting existing path as JSON.
+        Err(matchit::InsertError::Conflict { with }) => {
+            let body = serde_json::json!({ "error": "conflict", "with": with });
+            match serde_json::to_string(&body) {
+                Ok(json) => Box::into_raw(RouterResult::error_with_data(-7, json)),
+                Err(_) => Box::into_raw(RouterResult::error(-7)),
+            }
+        }
         Err(_) => Box::into_raw(RouterResult::error(-5)),
     }
 }
 
+// Joins a scope prefix and a route path with exactly one separating slash,
+// e.g. ("/api/", "/users") -> "/api/users".
+fn join_scope_path(prefix: &str, path: &str) -> String {
+    let trimmed_prefix = prefix.trim_end_matches('/');
+    let trimmed_path = path.trim_start_matches('/');
+    if trimmed_prefix.is_empty() {
+        format!("/{}", trimmed_path)
+    } else {
+        format!("{}/{}", trimmed_prefix, trimmed_path)
+    }
+}
+
+fn prefix_has_catch_all(prefix: &str) -> bool {
+    prefix.split('/').any(|segment| segment.starts_with("{*"))
+}
+
+// Builds a `RouteMeta` from a batch/scope route object, defaulting the
+// optional `name`, `middleware`, and `constraints` fields to empty so plain
+// `{"method", "path", "handler"}` entries keep working unchanged.
+fn route_meta_from_json(route: &serde_json::Value, handler: &str) -> RouteMeta {
+    let name = route["name"].as_str().map(|s| s.to_string());
+    let middleware = route["middleware"].as_array()
+        .map(|entries| entries.iter().filter_map(|v| v.as_str().map(|s| s.to_string())).collect())
+        .unwrap_or_default();
+    let constraints = route["constraints"].as_object()
+        .map(|obj| obj.iter()
+            .filter_map(|(k, v)| v.as_str().map(|s| (k.clone(), s.to_string())))
+            .collect())
+        .unwrap_or_default();
+
+    RouteMeta {
+        handler: handler.to_string(),
+        name,
+        middleware,
+        constraints,
+    }
+}
+
+/// Mount a batch of routes under a shared path prefix, joining separators so
+/// `"/api/"` + `"/users"` becomes `"/api/users"` rather than `"/api//users"`.
+#[no_mangle]
+pub extern "C" fn router_mount_scope(
+    handle: *mut c_void,
+    prefix: *const c_char,
+    routes_json: *const c_char,
+    len: usize,
+) -> *mut RouterResult {
+    if prefix.is_null() || routes_json.is_null() {
+        return Box::into_raw(RouterResult::error(-1));
+    }
+
+    let router = match unsafe { handle_ref(handle) } {
+        Some(router) => router,
+        None => return Box::into_raw(RouterResult::error(-1)),
+    };
+
+    let prefix_str = match unsafe { CStr::from_ptr(prefix) }.to_str() {
+        Ok(s) => s,
+        Err(_) => return Box::into_raw(RouterResult::error(-2)),
+    };
+
+    // A prefix that itself ends in a catch-all can never have further
+    // segments appended to it.
+    if prefix_has_catch_all(prefix_str) {
+        return Box::into_raw(RouterResult::error(-8));
+    }
+
+    let data_slice = unsafe { std::slice::from_raw_parts(routes_json as *const u8, len) };
+    let json_str = match std::str::from_utf8(data_slice) {
+        Ok(s) => s,
+        Err(_) => return Box::into_raw(RouterResult::error(-3)),
+    };
+
+    let routes: Vec<serde_json::Value> = match serde_json::from_str(json_str) {
+        Ok(routes) => routes,
+        Err(_) => return Box::into_raw(RouterResult::error(-4)),
+    };
+
+    let mut router = router.write().unwrap();
+    let mut added_count = 0;
+    let total_routes = routes.len();
+
+    for route in routes {
+        if let (Some(method), Some(path), Some(handler)) = (
+            route["method"].as_str(),
+            route["path"].as_str(),
+            route["handler"].as_str(),
+        ) {
+            let mounted_path = join_scope_path(prefix_str, path);
+            let meta = route_meta_from_json(&route, handler);
+            if router.add_route(method, &mounted_path, meta).is_ok() {
+                added_count += 1;
+            }
+        }
+    }
+
+    let result = serde_json::json!({
+        "added": added_count,
+        "total": total_routes
+    });
+
+    match serde_json::to_string(&result) {
+        Ok(json) => Box::into_raw(RouterResult::success(json)),
+        Err(_) => Box::into_raw(RouterResult::error(-5)),
+    }
+}
+
+/// Set the handler invoked when no route (exact-method, `ANY`, nor wildcard)
+/// matches, instead of returning a bare `-404`.
+#[no_mangle]
+pub extern "C" fn router_set_fallback(handle: *mut c_void, handler_id: *const c_char) -> *mut RouterResult {
+    if handler_id.is_null() {
+        return Box::into_raw(RouterResult::error(-1));
+    }
+
+    let router = match unsafe { handle_ref(handle) } {
+        Some(router) => router,
+        None => return Box::into_raw(RouterResult::error(-1)),
+    };
+
+    let handler_str = match unsafe { CStr::from_ptr(handler_id) }.to_str() {
+        Ok(s) => s,
+        Err(_) => return Box::into_raw(RouterResult::error(-2)),
+    };
+
+    router.write().unwrap().set_fallback(handler_str);
+    Box::into_raw(RouterResult::success("OK".to_string()))
+}
+
 /// Match a route
 #[no_mangle]
 pub extern "C" fn router_match(
+    handle: *mut c_void,
     method: *const c_char,
     path: *const c_char,
 ) -> *mut RouterResult {
@@ -181,6 +515,11 @@ pub extern "C" fn router_match(
         return Box::into_raw(RouterResult::error(-1));
     }
 
+    let router = match unsafe { handle_ref(handle) } {
+        Some(router) => router,
+        None => return Box::into_raw(RouterResult::error(-1)),
+    };
+
     let method_str = match unsafe { CStr::from_ptr(method) }.to_str() {
         Ok(s) => s,
         Err(_) => return Box::into_raw(RouterResult::error(-2)),
@@ -191,14 +530,17 @@ pub extern "C" fn router_match(
         Err(_) => return Box::into_raw(RouterResult::error(-3)),
     };
 
-    let router = get_router();
+    let router = router.read().unwrap();
     match router.match_route(method_str, path_str) {
-        Some((handler_id, params)) => {
+        Some((meta, params)) => {
             let result = serde_json::json!({
-                "handler": handler_id,
-                "params": params.into_iter().collect::<HashMap<String, String>>()
+                "handler": meta.handler,
+                "params": params.into_iter().collect::<HashMap<String, String>>(),
+                "name": meta.name,
+                "middleware": meta.middleware,
+                "constraints": meta.constraints,
             });
-            
+
             match serde_json::to_string(&result) {
                 Ok(json) => Box::into_raw(RouterResult::success(json)),
                 Err(_) => Box::into_raw(RouterResult::error(-4)),
@@ -210,18 +552,26 @@ pub extern "C" fn router_match(
 
 /// Clear route cache
 #[no_mangle]
-pub extern "C" fn router_clear_cache() -> *mut RouterResult {
-    let router = get_router();
-    router.clear_cache();
+pub extern "C" fn router_clear_cache(handle: *mut c_void) -> *mut RouterResult {
+    let router = match unsafe { handle_ref(handle) } {
+        Some(router) => router,
+        None => return Box::into_raw(RouterResult::error(-1)),
+    };
+
+    router.write().unwrap().clear_cache();
     Box::into_raw(RouterResult::success("OK".to_string()))
 }
 
 /// Get router statistics
 #[no_mangle]
-pub extern "C" fn router_get_stats() -> *mut RouterResult {
-    let router = get_router();
-    let stats = router.get_stats();
-    
+pub extern "C" fn router_get_stats(handle: *mut c_void) -> *mut RouterResult {
+    let router = match unsafe { handle_ref(handle) } {
+        Some(router) => router,
+        None => return Box::into_raw(RouterResult::error(-1)),
+    };
+
+    let stats = router.read().unwrap().get_stats();
+
     match serde_json::to_string(&stats) {
         Ok(json) => Box::into_raw(RouterResult::success(json)),
         Err(_) => Box::into_raw(RouterResult::error(-1)),
@@ -231,6 +581,7 @@ pub extern "C" fn router_get_stats() -> *mut RouterResult {
 /// Batch add multiple routes
 #[no_mangle]
 pub extern "C" fn router_batch_add_routes(
+    handle: *mut c_void,
     routes_json: *const c_char,
     len: usize,
 ) -> *mut RouterResult {
@@ -238,6 +589,11 @@ pub extern "C" fn router_batch_add_routes(
         return Box::into_raw(RouterResult::error(-1));
     }
 
+    let router = match unsafe { handle_ref(handle) } {
+        Some(router) => router,
+        None => return Box::into_raw(RouterResult::error(-1)),
+    };
+
     let data_slice = unsafe { std::slice::from_raw_parts(routes_json as *const u8, len) };
     let json_str = match std::str::from_utf8(data_slice) {
         Ok(s) => s,
@@ -249,7 +605,7 @@ pub extern "C" fn router_batch_add_routes(
         Err(_) => return Box::into_raw(RouterResult::error(-3)),
     };
 
-    let router = get_router();
+    let mut router = router.write().unwrap();
     let mut added_count = 0;
     let total_routes = routes.len();
 
@@ -259,7 +615,8 @@ pub extern "C" fn router_batch_add_routes(
             route["path"].as_str(),
             route["handler"].as_str(),
         ) {
-            if router.add_route(method, path, handler).is_ok() {
+            let meta = route_meta_from_json(&route, handler);
+            if router.add_route(method, path, meta).is_ok() {
                 added_count += 1;
             }
         }
@@ -294,6 +651,6 @@ pub extern "C" fn free_router_result(result: *mut RouterResult) {
 /// Get router capabilities
 #[no_mangle]
 pub extern "C" fn get_router_capabilities() -> c_int {
-    // Bit flags: 1=radix_tree, 2=caching, 4=batch_operations, 8=statistics
-    1 | 2 | 4 | 8
-}
\ No newline at end of file
+    // Bit flags: 1=radix_tree, 2=caching, 4=batch_operations, 8=statistics, 16=wildcard
+    1 | 2 | 4 | 8 | 16
+}